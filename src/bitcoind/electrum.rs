@@ -0,0 +1,120 @@
+use crate::bitcoind::{
+    backend::{BitcoinInterface, UtxoInfo},
+    interface::ChainTip,
+    BitcoindError,
+};
+
+use revault_tx::bitcoin::{
+    util::bip158::BlockFilter, Amount, BlockHash, OutPoint, Transaction, Txid,
+};
+
+use electrum_client::ElectrumApi;
+
+/// A read-mostly `BitcoinInterface` backed by an Electrum server, for operators who don't want to
+/// run a local archival bitcoind. Like `EsploraClient`, we don't hold a feebump wallet here:
+/// `feebump_coins` is always empty and `sign_feebump_tx` is a no-op, so Cancel transactions go
+/// out without a feebump input (relying on their own feerate alone) when this backend is used.
+pub struct ElectrumClient {
+    client: electrum_client::Client,
+}
+
+impl ElectrumClient {
+    pub fn new(addr: &str) -> Result<Self, BitcoindError> {
+        let client = electrum_client::Client::new(addr)
+            .map_err(|e| BitcoindError::Custom(format!("Connecting to Electrum: '{}'", e)))?;
+        Ok(Self { client })
+    }
+}
+
+impl BitcoinInterface for ElectrumClient {
+    fn chain_tip(&self) -> ChainTip {
+        loop {
+            match self.client.block_headers_subscribe() {
+                Ok(notif) => {
+                    let height = notif.height as i32;
+                    return ChainTip {
+                        height,
+                        hash: notif.header.block_hash(),
+                    };
+                }
+                Err(e) => {
+                    log::warn!(
+                        "Error fetching the chain tip from Electrum: '{}'. Retrying.",
+                        e
+                    );
+                    std::thread::sleep(std::time::Duration::from_secs(1));
+                }
+            }
+        }
+    }
+
+    fn block_hash(&self, height: i32) -> BlockHash {
+        loop {
+            match self.client.block_header(height as usize) {
+                Ok(header) => return header.block_hash(),
+                Err(e) => {
+                    log::warn!(
+                        "Error fetching the block hash at height '{}' from Electrum: '{}'. \
+                         Retrying.",
+                        height,
+                        e
+                    );
+                    std::thread::sleep(std::time::Duration::from_secs(1));
+                }
+            }
+        }
+    }
+
+    fn utxoinfo(&self, outpoint: &OutPoint) -> Option<UtxoInfo> {
+        // `script_get_history` only tells us the *creating* transaction confirmed, not that the
+        // output is still unspent: query the UTXO set directly instead, as the trait requires.
+        let tx = self.client.transaction_get(&outpoint.txid).ok()?;
+        let script = tx.output.get(outpoint.vout as usize)?.script_pubkey.clone();
+        let utxo = self
+            .client
+            .script_list_unspent(&script)
+            .ok()?
+            .into_iter()
+            .find(|u| u.tx_hash == outpoint.txid && u.tx_pos as u32 == outpoint.vout)?;
+        if utxo.height <= 0 {
+            // Unconfirmed (height is 0 or negative in Electrum's convention).
+            return None;
+        }
+        let tip = self.chain_tip();
+        let conf_height = utxo.height as i32;
+        Some(UtxoInfo {
+            bestblock: tip.hash,
+            confirmations: tip.height.checked_sub(conf_height)?.checked_add(1)? as u32,
+        })
+    }
+
+    fn broadcast_tx(&self, tx: &Transaction) -> Result<(), BitcoindError> {
+        self.client
+            .transaction_broadcast(tx)
+            .map(|_: Txid| ())
+            .map_err(|e| BitcoindError::Custom(format!("Broadcasting through Electrum: '{}'", e)))
+    }
+
+    fn feebump_coins(&self) -> Vec<(OutPoint, Amount)> {
+        // No wallet behind an Electrum-only backend: operators using this backend are expected
+        // to run their feebump wallet separately and fund Cancels out of band.
+        vec![]
+    }
+
+    fn sign_feebump_tx(&self, _tx: &mut Transaction) -> Result<(), BitcoindError> {
+        Ok(())
+    }
+
+    fn estimate_feerate(&self, conf_target: u16) -> Option<u64> {
+        let btc_per_kvb = self.client.estimate_fee(conf_target as usize).ok()?;
+        if btc_per_kvb <= 0.0 {
+            return None;
+        }
+        Some((btc_per_kvb * 100_000.0) as u64)
+    }
+
+    fn block_filter(&self, _height: i32) -> Option<BlockFilter> {
+        // Electrum doesn't expose BIP158 filters: callers always fall back to a direct scan.
+        None
+    }
+}
@@ -0,0 +1,104 @@
+use crate::bitcoind::{
+    backend::{BitcoinInterface, UtxoInfo},
+    interface::ChainTip,
+    BitcoindError,
+};
+
+use revault_tx::bitcoin::{util::bip158::BlockFilter, Amount, BlockHash, OutPoint, Transaction};
+
+/// A read-mostly `BitcoinInterface` backed by an Esplora server, for operators who don't want to
+/// run a local archival bitcoind. We don't hold a feebump wallet here: `feebump_coins` is always
+/// empty and `sign_feebump_tx` is a no-op, so Cancel transactions go out without a feebump input
+/// (relying on their own feerate alone) when this backend is used.
+pub struct EsploraClient {
+    client: esplora_client::BlockingClient,
+}
+
+impl EsploraClient {
+    pub fn new(base_url: &str) -> Result<Self, BitcoindError> {
+        let client = esplora_client::Builder::new(base_url)
+            .build_blocking()
+            .map_err(|e| BitcoindError::Custom(format!("Building Esplora client: '{}'", e)))?;
+        Ok(Self { client })
+    }
+}
+
+impl BitcoinInterface for EsploraClient {
+    fn chain_tip(&self) -> ChainTip {
+        loop {
+            match self
+                .client
+                .get_height()
+                .and_then(|h| self.client.get_block_hash(h).map(|hash| (h as i32, hash)))
+            {
+                Ok((height, hash)) => return ChainTip { height, hash },
+                Err(e) => {
+                    log::warn!(
+                        "Error fetching the chain tip from Esplora: '{}'. Retrying.",
+                        e
+                    );
+                    std::thread::sleep(std::time::Duration::from_secs(1));
+                }
+            }
+        }
+    }
+
+    fn block_hash(&self, height: i32) -> BlockHash {
+        loop {
+            match self.client.get_block_hash(height as u32) {
+                Ok(hash) => return hash,
+                Err(e) => {
+                    log::warn!(
+                        "Error fetching the block hash at height '{}' from Esplora: '{}'. \
+                         Retrying.",
+                        height,
+                        e
+                    );
+                    std::thread::sleep(std::time::Duration::from_secs(1));
+                }
+            }
+        }
+    }
+
+    fn utxoinfo(&self, outpoint: &OutPoint) -> Option<UtxoInfo> {
+        let status = self.client.get_output_status(&outpoint.txid, outpoint.vout as u64).ok()??;
+        if !status.spent {
+            let tip_height = self.client.get_height().ok()?;
+            let tx_status = self.client.get_tx_status(&outpoint.txid).ok()?;
+            let conf_height = tx_status.block_height?;
+            return Some(UtxoInfo {
+                bestblock: self.block_hash(tip_height as i32),
+                confirmations: tip_height.checked_sub(conf_height)?.checked_add(1)?,
+            });
+        }
+        None
+    }
+
+    fn broadcast_tx(&self, tx: &Transaction) -> Result<(), BitcoindError> {
+        self.client
+            .broadcast(tx)
+            .map_err(|e| BitcoindError::Custom(format!("Broadcasting through Esplora: '{}'", e)))
+    }
+
+    fn feebump_coins(&self) -> Vec<(OutPoint, Amount)> {
+        // No wallet behind an Esplora-only backend: operators using this backend are expected to
+        // run their feebump wallet separately and fund Cancels out of band.
+        vec![]
+    }
+
+    fn sign_feebump_tx(&self, _tx: &mut Transaction) -> Result<(), BitcoindError> {
+        Ok(())
+    }
+
+    fn estimate_feerate(&self, conf_target: u16) -> Option<u64> {
+        let estimates = self.client.get_fee_estimates().ok()?;
+        estimates
+            .get(&conf_target.to_string())
+            .map(|feerate_vb| *feerate_vb as u64)
+    }
+
+    fn block_filter(&self, _height: i32) -> Option<BlockFilter> {
+        // Esplora doesn't expose BIP158 filters: callers always fall back to a direct scan.
+        None
+    }
+}
@@ -2,7 +2,7 @@ mod bitcoind;
 mod config;
 mod keys;
 
-use bitcoind::{load_watchonly_wallet, start_bitcoind, wait_bitcoind_synced};
+use bitcoind::{load_feebump_wallet, load_watchonly_wallet, start_bitcoind, wait_bitcoind_synced};
 use config::{config_folder_path, Config};
 use keys::read_or_create_noise_key;
 use revault_net::{
@@ -14,6 +14,7 @@ use revault_net::{
 use std::{env, fs, os::unix::fs::DirBuilderExt, path, process, time};
 
 const VAULT_WATCHONLY_FILENAME: &str = "vault_watchonly";
+const FEEBUMP_WATCHONLY_FILENAME: &str = "feebump_watchonly";
 const NOISE_KEY_FILENAME: &str = "noise_secret";
 
 fn parse_args(args: Vec<String>) -> Option<path::PathBuf> {
@@ -124,7 +125,16 @@ fn main() {
         log::error!("Error loading vault watchonly wallet: '{}'", e);
         process::exit(1);
     });
-    // TODO: load feebumping wallet too.
+
+    let mut feebump_watchonly_path = data_dir
+        .to_str()
+        .expect("Data dir must be valid unicode")
+        .to_string();
+    feebump_watchonly_path.push_str(FEEBUMP_WATCHONLY_FILENAME);
+    load_feebump_wallet(&bitcoind, feebump_watchonly_path).unwrap_or_else(|e| {
+        log::error!("Error loading fee-bumping wallet: '{}'", e);
+        process::exit(1);
+    });
 
     let mut noise_secret_path = data_dir.clone();
     noise_secret_path.push(path::Path::new(NOISE_KEY_FILENAME));
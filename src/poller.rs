@@ -1,29 +1,39 @@
 use crate::{
-    bitcoind::{
-        interface::{BitcoinD, ChainTip},
-        BitcoindError,
-    },
+    bitcoind::{backend::BitcoinInterface, interface::ChainTip, BitcoindError},
     config::Config,
     database::{
-        db_cancel_signatures, db_canceling_vaults, db_del_vault, db_delegated_vaults, db_instance,
-        db_revoc_confirmed, db_should_cancel_vault, db_should_not_cancel_vault, db_update_tip,
-        db_vault, schema::DbVault, DatabaseError,
+        db_blockhash, db_cancel_attempts, db_cancel_signatures, db_canceling_vaults, db_del_vault,
+        db_delegated_vaults, db_instance, db_new_cancel_attempt, db_revoc_confirmed,
+        db_should_cancel_vault, db_should_not_cancel_vault, db_undo_revoc_confirmation,
+        db_undo_unvault, db_update_tip, db_unvaulted_vaults, db_vault, schema::DbVault,
+        DatabaseError,
     },
     plugins::{NewBlockInfo, VaultInfo},
 };
 use revault_tx::{
-    bitcoin::{consensus::encode, secp256k1},
+    bitcoin::{consensus::encode, secp256k1, Amount, Script},
     scripts::{DerivedCpfpDescriptor, DerivedDepositDescriptor, DerivedUnvaultDescriptor},
     transactions::{CancelTransaction, RevaultTransaction, UnvaultTransaction},
-    txins::{DepositTxIn, RevaultTxIn, UnvaultTxIn},
-    txouts::DepositTxOut,
+    txins::{DepositTxIn, FeeBumpTxIn, RevaultTxIn, UnvaultTxIn},
+    txouts::{DepositTxOut, FeeBumpTxOut},
 };
 
-use std::{convert::TryInto, path, thread};
+use std::{convert::TryInto, path, thread, time};
 
 /// How many blocks are we waiting to consider a consumed vault irreversably spent
 const REORG_WATCH_LIMIT: i32 = 288;
 
+/// If our last fee-bump attempt for a Cancel transaction is more than this many sats/vbyte below
+/// the currently estimated feerate, we consider it is lagging and try to bump it again.
+const FEEBUMP_TOLERANCE_SATVB: u64 = 1;
+
+/// How many blocks ahead we ask bitcoind to estimate the feerate for a Cancel transaction we
+/// want confirmed well before its CSV matures.
+const FEEBUMP_URGENT_CONF_TARGET: u16 = 2;
+
+/// A single P2WPKH feebump input adds roughly this many vbytes to a transaction.
+const FEEBUMP_INPUT_VSIZE: u64 = 70;
+
 /// An error happened in the main loop
 #[derive(Debug)]
 pub enum PollerError {
@@ -95,15 +105,176 @@ fn unvault_tx(
     )
 }
 
+// The vsize (in vbytes) of a Cancel transaction, rounded up. `max_weight` is the transaction's
+// weight without a feebump input, so the caller must add `FEEBUMP_INPUT_VSIZE` on top of this if
+// it goes on to include one.
+fn cancel_tx_vsize(cancel_tx: &CancelTransaction) -> u64 {
+    (cancel_tx.max_weight() as u64 + 3) / 4
+}
+
+// Pick a feebump coin large enough that, added to `cancel_vsize` (the Cancel transaction's own
+// vsize without a feebump input), the whole transaction reaches `target_feerate` sat/vbyte.
+// Returns `None` if we don't hold a suitable coin, in which case the Cancel transaction goes out
+// feebump-less and relies on its own feerate alone.
+fn feebump_input(
+    bitcoind: &impl BitcoinInterface,
+    cancel_vsize: u64,
+    target_feerate: u64,
+) -> Option<FeeBumpTxIn> {
+    let needed_sats = target_feerate.checked_mul(cancel_vsize.checked_add(FEEBUMP_INPUT_VSIZE)?)?;
+
+    bitcoind
+        .feebump_coins()
+        .into_iter()
+        .find(|(_, amount)| amount.as_sat() > needed_sats)
+        .map(|(outpoint, amount)| FeeBumpTxIn::new(outpoint, FeeBumpTxOut::new(amount)))
+}
+
+// Sign, finalize and broadcast a Cancel transaction for this vault, feebumped (if we hold a
+// suitable coin) for `target_feerate`. Records the attempt in the database so
+// `manage_cancel_attempts` knows when it needs to be bumped again.
+fn broadcast_cancel(
+    db_path: &path::Path,
+    secp: &secp256k1::Secp256k1<impl secp256k1::Verification>,
+    bitcoind: &impl BitcoinInterface,
+    db_vault: &DbVault,
+    unvault_tx: &UnvaultTransaction,
+    unvault_desc: &DerivedUnvaultDescriptor,
+    unvault_txin: UnvaultTxIn,
+    deposit_desc: &DerivedDepositDescriptor,
+    current_height: i32,
+    target_feerate: u64,
+) -> Result<(), PollerError> {
+    // Size the feebump input, if any, off the actual vsize of this vault's Cancel transaction
+    // rather than a flat guess: a multisig revocation script's signatures dwarf the feebump
+    // input's own ~70 vbytes.
+    let probe_txin = unvault_tx.revault_unvault_txin(unvault_desc);
+    let cancel_vsize = CancelTransaction::new(
+        probe_txin,
+        None,
+        &deposit_desc,
+        /* FIXME: remove from the API */ 0,
+    )
+    .map(|tx| cancel_tx_vsize(&tx))
+    .unwrap_or(0);
+
+    let mut cancel_tx = CancelTransaction::new(
+        unvault_txin,
+        feebump_input(bitcoind, cancel_vsize, target_feerate),
+        &deposit_desc,
+        /* FIXME: remove from the API */ 0,
+    )
+    .expect("Can only fail if we have an insane feebumping input");
+
+    for db_sig in db_cancel_signatures(db_path, db_vault.id)? {
+        if let Err(e) = cancel_tx.add_cancel_sig(db_sig.pubkey, db_sig.signature, secp) {
+            log::error!(
+                "Error adding signature '{:?}' to Cancel transaction '{}': '{:?}'",
+                db_sig,
+                cancel_tx,
+                e
+            );
+        } else {
+            log::trace!(
+                "Added signature '{:?}' to Cancel transaction '{}'",
+                db_sig,
+                cancel_tx
+            );
+        }
+    }
+
+    if let Err(e) = cancel_tx.finalize(secp) {
+        log::error!(
+            "Error finalizing Cancel transaction '{}': '{:?}'",
+            cancel_tx,
+            e
+        );
+        return Ok(()); // Don't crash, though.
+    } else {
+        log::trace!("Finalized Cancel transaction '{}'", cancel_tx);
+    }
+
+    let txid = cancel_tx.txid();
+    let mut cancel_tx = cancel_tx.into_tx();
+    // The feebump input, if any, is ours: it must be signed by our own fee-bumping wallet.
+    if let Err(e) = bitcoind.sign_feebump_tx(&mut cancel_tx) {
+        log::error!(
+            "Error signing feebump input of Cancel transaction '{}': '{}'",
+            txid, e
+        );
+        return Ok(());
+    }
+
+    if let Err(e) = bitcoind.broadcast_tx(&cancel_tx) {
+        log::error!(
+            "Error broadcasting Cancel transaction '{}': '{:?}'",
+            encode::serialize_hex(&cancel_tx),
+            e
+        );
+        return Ok(());
+    }
+    log::debug!(
+        "Broadcasted Cancel transaction '{}'",
+        encode::serialize_hex(&cancel_tx)
+    );
+
+    db_new_cancel_attempt(db_path, db_vault.id, txid, target_feerate, current_height)?;
+
+    Ok(())
+}
+
+// Cheaply test whether `current_tip`'s block could contain an output paying one of `scripts`,
+// using bitcoind's BIP158 basic block filter when available. Filters can yield false positives
+// but never false negatives, so a `false` result here means it's safe to skip the RPC fallback
+// entirely; `true` (including when we have no filter to check against) means callers must still
+// confirm with a direct query.
+fn block_may_contain(
+    bitcoind: &impl BitcoinInterface,
+    current_tip: &ChainTip,
+    scripts: &[Script],
+) -> bool {
+    if scripts.is_empty() {
+        return false;
+    }
+
+    match bitcoind.block_filter(current_tip.height) {
+        Some(filter) => filter
+            .match_any(&current_tip.hash, &mut scripts.iter().map(Script::as_bytes))
+            .unwrap_or(true),
+        // No filter index on this node (requires `blockfilterindex=1`): always fall back.
+        None => true,
+    }
+}
+
 fn manage_cancel_attempts(
     db_path: &path::Path,
     secp: &secp256k1::Secp256k1<impl secp256k1::Verification>,
     config: &Config,
-    bitcoind: &BitcoinD,
+    bitcoind: &impl BitcoinInterface,
     current_tip: &ChainTip,
 ) -> Result<(), PollerError> {
     let canceling_vaults = db_canceling_vaults(db_path)?;
 
+    // A Cancel transaction pays back to the deposit script. Test this block's BIP158 filter
+    // against every deposit script we're still waiting a Cancel confirmation for, so we only pay
+    // for a `utxoinfo` RPC call per vault when the filter tells us it might be worth it.
+    let watched_scripts: Vec<Script> = canceling_vaults
+        .iter()
+        .filter(|v| v.revoc_height.is_none())
+        .map(|v| descriptors(secp, config, v).0.script_pubkey())
+        .collect();
+    let maybe_cancel_confirmed = block_may_contain(bitcoind, current_tip, &watched_scripts);
+
+    // A BIP158 basic filter also encodes the scriptPubKeys of every prevout spent in the block,
+    // so it equally tells us whether this block could have spent one of our still-unconfirmed
+    // Unvault outputs (by our Cancel, or by a thief's Spend past the CSV).
+    let unvault_scripts: Vec<Script> = canceling_vaults
+        .iter()
+        .filter(|v| v.revoc_height.is_none())
+        .map(|v| descriptors(secp, config, v).1.script_pubkey())
+        .collect();
+    let maybe_unvault_spent = block_may_contain(bitcoind, current_tip, &unvault_scripts);
+
     for db_vault in canceling_vaults {
         let (deposit_desc, unvault_desc, cpfp_desc) = descriptors(secp, config, &db_vault);
         let unvault_tx = match unvault_tx(&db_vault, &deposit_desc, &unvault_desc, &cpfp_desc) {
@@ -143,9 +314,13 @@ fn manage_cancel_attempts(
             continue;
         }
 
-        // Check if it just got confirmed.
+        // Check if it just got confirmed. No point asking bitcoind if the block's filter ruled
+        // out every deposit script we're watching for a Cancel confirmation.
         let cancel_outpoint = cancel_tx.deposit_txin(&deposit_desc).outpoint();
-        if let Some(utxoinfo) = bitcoind.utxoinfo(&cancel_outpoint) {
+        let cancel_utxoinfo = maybe_cancel_confirmed
+            .then(|| bitcoind.utxoinfo(&cancel_outpoint))
+            .flatten();
+        if let Some(utxoinfo) = cancel_utxoinfo {
             if utxoinfo.bestblock != current_tip.hash {
                 // TODO
             }
@@ -176,8 +351,10 @@ fn manage_cancel_attempts(
         // If the chain didn't change, and there is no Cancel UTXO at the best block there
         // are only 2 possibilities before the expiration of the CSV: either the Cancel
         // transaction is still unconfirmed (and therefore the Unvault UTXO is still present)
-        // or it was spent.
-        if bitcoind.utxoinfo(&unvault_outpoint).is_none() {
+        // or it was spent. No point asking bitcoind if the block's filter ruled out every
+        // Unvault scriptPubKey we're watching for a spend.
+        let unvault_spent = maybe_unvault_spent && bitcoind.utxoinfo(&unvault_outpoint).is_none();
+        if unvault_spent {
             if bitcoind.chain_tip().hash != current_tip.hash {
                 // TODO
             }
@@ -216,78 +393,75 @@ fn manage_cancel_attempts(
             }
         }
 
-        // Ok the Cancel is still unconfirmed.
+        // Ok the Cancel is still unconfirmed. Bump it if our last attempt is now lagging behind
+        // the feerate required to get it confirmed before the Unvault timelock matures.
         log::debug!(
             "Cancel transaction '{}' for vault at '{}' is still unconfirmed at height '{}'",
             cancel_tx.txid(),
             &db_vault.deposit_outpoint,
             current_tip.height
         );
+        let target_feerate = bitcoind
+            .estimate_feerate(FEEBUMP_URGENT_CONF_TARGET)
+            .unwrap_or(1);
+        let is_lagging = db_cancel_attempts(db_path, db_vault.id)?
+            .into_iter()
+            .last()
+            .map(|attempt| attempt.feerate + FEEBUMP_TOLERANCE_SATVB < target_feerate)
+            .unwrap_or(true);
+        if is_lagging && !unvault_spent {
+            log::info!(
+                "Cancel transaction '{}' for vault at '{}' is lagging behind the required \
+                 feerate of '{}' sat/vb, attempting to fee-bump it.",
+                cancel_tx.txid(),
+                &db_vault.deposit_outpoint,
+                target_feerate
+            );
+            let unvault_txin = unvault_tx.revault_unvault_txin(&unvault_desc);
+            broadcast_cancel(
+                db_path,
+                secp,
+                bitcoind,
+                &db_vault,
+                &unvault_tx,
+                &unvault_desc,
+                unvault_txin,
+                &deposit_desc,
+                current_tip.height,
+                target_feerate,
+            )?;
+        }
     }
 
     Ok(())
 }
 
-// TODO: actual feebump computation, register attempt in db, ..
 fn revault(
     db_path: &path::Path,
     secp: &secp256k1::Secp256k1<impl secp256k1::Verification>,
-    bitcoind: &BitcoinD,
+    bitcoind: &impl BitcoinInterface,
     db_vault: &DbVault,
+    unvault_tx: &UnvaultTransaction,
+    unvault_desc: &DerivedUnvaultDescriptor,
     unvault_txin: UnvaultTxIn,
     deposit_desc: &DerivedDepositDescriptor,
+    current_height: i32,
 ) -> Result<(), PollerError> {
-    let mut cancel_tx = CancelTransaction::new(
+    let target_feerate = bitcoind
+        .estimate_feerate(FEEBUMP_URGENT_CONF_TARGET)
+        .unwrap_or(1);
+    broadcast_cancel(
+        db_path,
+        secp,
+        bitcoind,
+        db_vault,
+        unvault_tx,
+        unvault_desc,
         unvault_txin,
-        None,
-        &deposit_desc,
-        /* FIXME: remove from the API */ 0,
+        deposit_desc,
+        current_height,
+        target_feerate,
     )
-    .expect("Can only fail if we have an insane feebumping input");
-
-    for db_sig in db_cancel_signatures(db_path, db_vault.id)? {
-        if let Err(e) = cancel_tx.add_cancel_sig(db_sig.pubkey, db_sig.signature, secp) {
-            log::error!(
-                "Error adding signature '{:?}' to Cancel transaction '{}': '{:?}'",
-                db_sig,
-                cancel_tx,
-                e
-            );
-        } else {
-            log::trace!(
-                "Added signature '{:?}' to Cancel transaction '{}'",
-                db_sig,
-                cancel_tx
-            );
-        }
-    }
-
-    if let Err(e) = cancel_tx.finalize(secp) {
-        log::error!(
-            "Error finalizing Cancel transaction '{}': '{:?}'",
-            cancel_tx,
-            e
-        );
-        return Ok(()); // Don't crash, though.
-    } else {
-        log::trace!("Finalized Cancel transaction '{}'", cancel_tx);
-    }
-
-    let cancel_tx = cancel_tx.into_tx();
-    if let Err(e) = bitcoind.broadcast_tx(&cancel_tx) {
-        log::error!(
-            "Error broadcasting Cancel transaction '{}': '{:?}'",
-            encode::serialize_hex(&cancel_tx),
-            e
-        );
-    } else {
-        log::debug!(
-            "Broadcasted Cancel transaction '{}'",
-            encode::serialize_hex(&cancel_tx)
-        );
-    }
-
-    Ok(())
 }
 
 // Poll bitcoind for new Unvault UTxO of delegated vaults we are watching. Return info about each
@@ -296,12 +470,15 @@ fn check_for_unvault(
     db_path: &path::Path,
     secp: &secp256k1::Secp256k1<impl secp256k1::Verification>,
     config: &Config,
-    bitcoind: &BitcoinD,
+    bitcoind: &impl BitcoinInterface,
     current_tip: &ChainTip,
 ) -> Result<NewBlockInfo, PollerError> {
     let deleg_vaults = db_delegated_vaults(db_path)?;
     let mut new_attempts = vec![];
 
+    // Derive every watched Unvault transaction (and its scriptPubKey) once, so we can test this
+    // block's BIP158 filter before paying for a `utxoinfo` RPC call per vault.
+    let mut pending = Vec::with_capacity(deleg_vaults.len());
     for db_vault in deleg_vaults {
         let (deposit_desc, unvault_desc, cpfp_desc) = descriptors(secp, config, &db_vault);
         let unvault_tx = match unvault_tx(&db_vault, &deposit_desc, &unvault_desc, &cpfp_desc) {
@@ -313,8 +490,18 @@ fn check_for_unvault(
             }
         };
         let unvault_txin = unvault_tx.revault_unvault_txin(&unvault_desc);
+        let script = unvault_desc.script_pubkey();
+        pending.push((db_vault, unvault_tx, unvault_txin, script));
+    }
+
+    let watched_scripts: Vec<Script> = pending.iter().map(|(.., s)| s.clone()).collect();
+    let maybe_unvaulted = block_may_contain(bitcoind, current_tip, &watched_scripts);
 
-        if let Some(utxoinfo) = bitcoind.utxoinfo(&unvault_txin.outpoint()) {
+    for (db_vault, unvault_tx, unvault_txin, _) in pending {
+        let utxoinfo = maybe_unvaulted
+            .then(|| bitcoind.utxoinfo(&unvault_txin.outpoint()))
+            .flatten();
+        if let Some(utxoinfo) = utxoinfo {
             if current_tip.hash != utxoinfo.bestblock {
                 // TODO
             }
@@ -349,13 +536,75 @@ fn check_for_unvault(
     })
 }
 
+// Rough estimate of whether our feebump wallet still holds enough coins to fee-bump every
+// currently pending Cancel transaction at the feerate we'd currently aim for. This doesn't
+// account for coins already earmarked by an unconfirmed fee-bump, so it's only a lower bound on
+// how worried an operator should be.
+fn check_feebump_reserve(
+    db_path: &path::Path,
+    secp: &secp256k1::Secp256k1<impl secp256k1::Verification>,
+    config: &Config,
+    bitcoind: &impl BitcoinInterface,
+) -> Result<(), PollerError> {
+    let target_feerate = bitcoind
+        .estimate_feerate(FEEBUMP_URGENT_CONF_TARGET)
+        .unwrap_or(1);
+
+    // Vaults whose Cancel already confirmed don't need any more fee-bumping.
+    let canceling_vaults: Vec<DbVault> = db_canceling_vaults(db_path)?
+        .into_iter()
+        .filter(|v| v.revoc_height.is_none())
+        .collect();
+    let n_canceling = canceling_vaults.len() as u64;
+    let mut needed_sats: u64 = 0;
+    for db_vault in canceling_vaults {
+        let (deposit_desc, unvault_desc, cpfp_desc) = descriptors(secp, config, &db_vault);
+        let unvault_tx = match unvault_tx(&db_vault, &deposit_desc, &unvault_desc, &cpfp_desc) {
+            Ok(tx) => tx,
+            Err(e) => {
+                log::error!("Unexpected error deriving Unvault transaction: '{}'", e);
+                continue;
+            }
+        };
+        let unvault_txin = unvault_tx.revault_unvault_txin(&unvault_desc);
+        let cancel_vsize = match CancelTransaction::new(
+            unvault_txin,
+            None,
+            &deposit_desc,
+            /* FIXME: remove from the API */ 0,
+        ) {
+            Ok(tx) => cancel_tx_vsize(&tx),
+            Err(_) => continue,
+        };
+        needed_sats = needed_sats.saturating_add(
+            target_feerate.saturating_mul(cancel_vsize.saturating_add(FEEBUMP_INPUT_VSIZE)),
+        );
+    }
+
+    let available_sats: u64 = bitcoind
+        .feebump_coins()
+        .iter()
+        .map(|(_, amount)| amount.as_sat())
+        .sum();
+
+    if available_sats < needed_sats {
+        log::warn!(
+            "Feebump wallet reserve is low: holding '{}' sats but might need up to '{}' sats to \
+             fee-bump all '{}' pending Cancel transactions at the current feerate of '{}' sat/vb.",
+            available_sats, needed_sats, n_canceling, target_feerate
+        );
+    }
+
+    Ok(())
+}
+
 // Poll each of our plugins for vaults to be revaulted given the updates to our vaults' state
 // (which might be an empty set) in the latest block.
 fn maybe_revault(
     db_path: &path::Path,
     secp: &secp256k1::Secp256k1<impl secp256k1::Verification>,
     config: &Config,
-    bitcoind: &BitcoinD,
+    bitcoind: &impl BitcoinInterface,
     block_height: i32,
     block_info: &NewBlockInfo,
 ) -> Result<(), PollerError> {
@@ -400,8 +649,11 @@ fn maybe_revault(
                 secp,
                 bitcoind,
                 &db_vault,
+                &unvault_tx,
+                &unvault_desc,
                 unvault_txin,
                 &deposit_desc,
+                block_height,
             )?;
         } else {
             // FIXME: should we crash? This must never happen.
@@ -420,11 +672,21 @@ fn new_block(
     db_path: &path::Path,
     secp: &secp256k1::Secp256k1<impl secp256k1::Verification>,
     config: &Config,
-    bitcoind: &BitcoinD,
+    bitcoind: &impl BitcoinInterface,
     current_tip: &ChainTip,
+    prev_height: i32,
 ) -> Result<(), PollerError> {
+    // `check_for_unvault` and `manage_cancel_attempts` only test `current_tip`'s BIP158 filter,
+    // which only ever describes a single block. If the caller ever skipped a height to get here,
+    // that block's contents would be silently missed rather than causing a false negative.
+    debug_assert_eq!(
+        current_tip.height,
+        prev_height + 1,
+        "new_block must be called for a single block right after the last one we processed"
+    );
+
     // Update the fee-bumping reserves estimates
-    // TODO
+    check_feebump_reserve(db_path, secp, config, bitcoind)?;
 
     // Any vault to forget and feebump coins to unregister?
     // TODO
@@ -458,12 +720,183 @@ fn new_block(
     Ok(())
 }
 
+// Walk back from `from_height` until we find a height at which our stored blockhash still
+// matches bitcoind's, and return it. We normally persist the blockhash of every height we scan
+// (see `main_loop`), but tolerate a missing record (eg from before that guarantee held) by just
+// skipping it rather than panicking: we simply can't rule out that height as the fork point and
+// keep walking back.
+fn reorg_fork_point(
+    db_path: &path::Path,
+    bitcoind: &impl BitcoinInterface,
+    from_height: i32,
+) -> Result<i32, PollerError> {
+    let mut height = from_height;
+
+    while height > 0 {
+        match db_blockhash(db_path, height)? {
+            Some(stored_hash) if bitcoind.block_hash(height) == stored_hash => return Ok(height),
+            Some(_) => {}
+            None => log::warn!(
+                "No stored blockhash for height '{}' while looking for the reorg's common \
+                 ancestor, skipping it.",
+                height
+            ),
+        }
+        height -= 1;
+    }
+
+    Ok(0)
+}
+
+// Undo whatever state changes happened strictly after `fork_height`, so that the subsequent
+// forward re-scan from `fork_height` re-derives the right state for every vault.
+// NOTE: vaults fully forgotten (see `REORG_WATCH_LIMIT` in `manage_cancel_attempts`) are never
+// rolled back here, as they are kept around precisely until a reorg this deep is implausible.
+fn rollback_vaults(db_path: &path::Path, fork_height: i32) -> Result<(), PollerError> {
+    for db_vault in db_unvaulted_vaults(db_path)? {
+        if let Some(unvault_height) = db_vault.unvault_height {
+            if unvault_height > fork_height {
+                log::info!(
+                    "Rolling back Unvault state for vault at '{}', unvaulted at height '{}' \
+                     which is now past the reorg fork point '{}'.",
+                    &db_vault.deposit_outpoint,
+                    unvault_height,
+                    fork_height
+                );
+                db_undo_unvault(db_path, db_vault.id)?;
+                continue;
+            }
+        }
+
+        if let Some(revoc_height) = db_vault.revoc_height {
+            if revoc_height > fork_height {
+                log::info!(
+                    "Rolling back Cancel confirmation for vault at '{}', confirmed at height \
+                     '{}' which is now past the reorg fork point '{}'.",
+                    &db_vault.deposit_outpoint,
+                    revoc_height,
+                    fork_height
+                );
+                db_undo_revoc_confirmation(db_path, db_vault.id)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// A reorg was detected: our stored tip's blockhash at some height no longer matches bitcoind's.
+// Find the common ancestor, roll back any per-vault state that was derived from now-orphaned
+// blocks, and rewind the DB tip so the main loop re-scans forward from there.
+fn handle_reorg(
+    db_path: &path::Path,
+    bitcoind: &impl BitcoinInterface,
+    stale_height: i32,
+) -> Result<(), PollerError> {
+    log::warn!(
+        "Reorg detected: our blockhash at height '{}' doesn't match bitcoind's anymore.",
+        stale_height
+    );
+
+    let fork_height = reorg_fork_point(db_path, bitcoind, stale_height)?;
+    log::info!("Common ancestor with bitcoind found at height '{}'.", fork_height);
+
+    rollback_vaults(db_path, fork_height)?;
+
+    let fork_hash = bitcoind.block_hash(fork_height);
+    db_update_tip(db_path, fork_height, fork_hash)?;
+
+    Ok(())
+}
+
+// Subscribes to bitcoind's ZMQ `hashblock` notifications so `main_loop` can wake up as soon as a
+// new block is connected instead of waiting for the next poll. Reconnects transparently on
+// socket errors; callers should still reconcile against `bitcoind.chain_tip()` on every wake-up,
+// as a block could in principle be missed during a reconnect gap.
+struct ZmqBlockListener {
+    addr: String,
+    socket: zmq::Socket,
+}
+
+impl ZmqBlockListener {
+    fn new(addr: &str) -> Result<Self, zmq::Error> {
+        let ctx = zmq::Context::new();
+        let socket = ctx.socket(zmq::SUB)?;
+        socket.connect(addr)?;
+        socket.set_subscribe(b"hashblock")?;
+        Ok(Self {
+            addr: addr.to_string(),
+            socket,
+        })
+    }
+
+    // Block until a new-block notification comes in or `timeout` elapses, whichever is first.
+    // The timeout acts as a keepalive: we fall back to the poll interval on every wake-up anyway,
+    // so missing (or duplicating) a wake-up here is harmless.
+    fn wait_new_block(&mut self, timeout: time::Duration) {
+        match self.socket.poll(zmq::POLLIN, timeout.as_millis() as i64) {
+            Ok(n) if n > 0 => {
+                if let Err(e) = self.socket.recv_multipart(0) {
+                    log::warn!(
+                        "Error reading from bitcoind's ZMQ socket: '{}'. Reconnecting.",
+                        e
+                    );
+                    self.reconnect();
+                    // Reconnecting didn't consume `timeout`, so sleep for it ourselves: without
+                    // this, a socket stuck in this state would have us busy-loop instead of
+                    // falling back to the poll interval like a missing/never-configured socket
+                    // already does.
+                    thread::sleep(timeout);
+                }
+            }
+            Ok(_) => {} // Timed out, the caller will poll and come back.
+            Err(e) => {
+                log::warn!(
+                    "Error polling bitcoind's ZMQ socket: '{}'. Reconnecting.",
+                    e
+                );
+                self.reconnect();
+                thread::sleep(timeout);
+            }
+        }
+    }
+
+    fn reconnect(&mut self) {
+        match Self::new(&self.addr) {
+            Ok(new_self) => *self = new_self,
+            Err(e) => log::error!(
+                "Error reconnecting to bitcoind's ZMQ endpoint '{}': '{}'",
+                self.addr,
+                e
+            ),
+        }
+    }
+}
+
 pub fn main_loop(
     db_path: &path::Path,
     secp: &secp256k1::Secp256k1<impl secp256k1::Verification>,
     config: &Config,
-    bitcoind: &BitcoinD,
+    bitcoind: &impl BitcoinInterface,
 ) -> Result<(), PollerError> {
+    let mut zmq_listener =
+        config
+            .bitcoind_config
+            .zmq_block_addr
+            .as_ref()
+            .and_then(|addr| match ZmqBlockListener::new(addr) {
+                Ok(listener) => Some(listener),
+                Err(e) => {
+                    log::error!(
+                        "Error connecting to bitcoind's ZMQ endpoint '{}': '{}'. Falling back \
+                         to polling.",
+                        addr,
+                        e
+                    );
+                    None
+                }
+            });
+
     loop {
         let db_instance = db_instance(db_path)?;
         let bitcoind_tip = bitcoind.chain_tip();
@@ -471,15 +904,37 @@ pub fn main_loop(
         if bitcoind_tip.height > db_instance.tip_blockheight {
             let curr_tip_hash = bitcoind.block_hash(db_instance.tip_blockheight);
             if db_instance.tip_blockheight != 0 && curr_tip_hash != db_instance.tip_blockhash {
-                panic!("No reorg handling yet");
+                handle_reorg(db_path, bitcoind, db_instance.tip_blockheight)?;
+                continue;
             }
 
-            new_block(db_path, secp, config, bitcoind, &bitcoind_tip)?;
-            db_update_tip(db_path, bitcoind_tip.height, bitcoind_tip.hash)?;
+            // Process every block between our last tip and bitcoind's one by one, even after a
+            // long downtime or a burst of blocks: we need to persist each height's blockhash (for
+            // `reorg_fork_point` to find the common ancestor later) and a BIP158 filter only ever
+            // describes a single block, never a range, so `new_block` must be called once per
+            // height rather than jumping straight to the latest tip.
+            let mut height = db_instance.tip_blockheight + 1;
+            while height <= bitcoind_tip.height {
+                let tip = ChainTip {
+                    height,
+                    hash: bitcoind.block_hash(height),
+                };
+                new_block(db_path, secp, config, bitcoind, &tip, height - 1)?;
+                db_update_tip(db_path, tip.height, tip.hash)?;
+                height += 1;
+            }
         } else if bitcoind_tip.hash != db_instance.tip_blockhash {
-            panic!("No reorg handling yet");
+            handle_reorg(db_path, bitcoind, db_instance.tip_blockheight)?;
+            continue;
         }
 
-        thread::sleep(config.bitcoind_config.poll_interval_secs);
+        // With ZMQ configured we still reconcile against `chain_tip()` above on every wake-up,
+        // so a block missed during a reconnect gap is simply picked up on the following one.
+        match zmq_listener {
+            Some(ref mut listener) => {
+                listener.wait_new_block(config.bitcoind_config.poll_interval_secs)
+            }
+            None => thread::sleep(config.bitcoind_config.poll_interval_secs),
+        }
     }
 }